@@ -0,0 +1,192 @@
+//! Typed ClickHouse errors, with enough structure attached to decide whether
+//! a failure is worth retrying.
+//!
+//! ClickHouse's HTTP interface reports failures as a 5xx/4xx status plus a
+//! plaintext body that (usually) starts with `Code: <n>.`, where `<n>` is
+//! one of the codes listed in ClickHouse's `system.errors` table. We only
+//! care about a handful of them here: apart from a 408/504 status (always
+//! treated as a timeout), every other code, or a body with no code at all,
+//! is classified as a non-retryable `BadQuery` whatever the HTTP status.
+
+use std::time::Duration;
+
+use rand::Rng;
+
+/// ClickHouse's `TOO_MANY_SIMULTANEOUS_QUERIES` error code.
+const CODE_TOO_MANY_SIMULTANEOUS_QUERIES: u32 = 202;
+/// ClickHouse's `MEMORY_LIMIT_EXCEEDED` error code.
+const CODE_MEMORY_LIMIT_EXCEEDED: u32 = 241;
+/// ClickHouse's `TIMEOUT_EXCEEDED` error code.
+const CODE_TIMEOUT_EXCEEDED: u32 = 159;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClickHouseError {
+    /// A malformed or type-invalid query. Never worth retrying: the same
+    /// query will fail the same way every time.
+    #[error("ClickHouse rejected the query: {message}")]
+    BadQuery { message: String },
+    /// ClickHouse is temporarily unable to accept the query because it's
+    /// overloaded (too many concurrent queries, over its memory limit, ...).
+    /// Worth retrying after a backoff once load drops.
+    #[error("ClickHouse is overloaded (code {code}): {message}")]
+    Overloaded { code: u32, message: String },
+    /// The query didn't finish within ClickHouse's configured timeout.
+    #[error("ClickHouse query timed out: {message}")]
+    Timeout { message: String },
+    /// We couldn't complete the HTTP round trip at all (DNS, connect,
+    /// connection reset, ...). A 5xx response without a ClickHouse error
+    /// code (e.g. from a reverse proxy) is classified as `BadQuery` by
+    /// [`ClickHouseError::from_response`], not as `Transport`.
+    #[error("Failed to reach ClickHouse: {message}")]
+    Transport { message: String },
+}
+
+impl ClickHouseError {
+    /// A catch-all constructor for failures that never reached ClickHouse at
+    /// all (the request couldn't be sent, the response couldn't be read).
+    pub(crate) fn new(message: impl Into<String>) -> Self {
+        Self::Transport {
+            message: message.into(),
+        }
+    }
+
+    /// Whether this error is transient and the same query is worth
+    /// attempting again after a backoff.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Overloaded { .. } | Self::Timeout { .. } | Self::Transport { .. })
+    }
+
+    /// Classifies a non-success HTTP response from ClickHouse into a typed
+    /// variant, using the `Code: <n>` prefix ClickHouse embeds in its
+    /// plaintext error bodies when available.
+    pub fn from_response(status: reqwest::StatusCode, body: &str) -> Self {
+        if status == reqwest::StatusCode::GATEWAY_TIMEOUT || status == reqwest::StatusCode::REQUEST_TIMEOUT {
+            return Self::Timeout {
+                message: body.to_string(),
+            };
+        }
+        if let Some(code) = Self::extract_code(body) {
+            match code {
+                CODE_TIMEOUT_EXCEEDED => {
+                    return Self::Timeout {
+                        message: body.to_string(),
+                    }
+                }
+                CODE_TOO_MANY_SIMULTANEOUS_QUERIES | CODE_MEMORY_LIMIT_EXCEEDED => {
+                    return Self::Overloaded {
+                        code,
+                        message: body.to_string(),
+                    }
+                }
+                _ => {}
+            }
+        }
+        // Only a code we've explicitly classified above is worth retrying. An
+        // unrecognized code (or no code at all) defaults to `BadQuery`, even
+        // on a 5xx status: a ClickHouse 5xx is just as often a deterministic
+        // query bug (e.g. a function throwing) as it is overload, and
+        // retrying a query that can never succeed just wastes the backoff
+        // budget.
+        Self::BadQuery {
+            message: body.to_string(),
+        }
+    }
+
+    /// Extracts the numeric code from a ClickHouse error body of the form
+    /// `"Code: 241. DB::Exception: Memory limit ... exceeded: ..."`.
+    fn extract_code(body: &str) -> Option<u32> {
+        let after = body.strip_prefix("Code: ")?;
+        let digits: String = after.chars().take_while(|c| c.is_ascii_digit()).collect();
+        digits.parse().ok()
+    }
+}
+
+/// Exponential backoff with full jitter for retrying a [`ClickHouseError`]
+/// that reports [`ClickHouseError::is_retryable`].
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(50),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay to wait before retry number `attempt` (0-indexed: `0` is the
+    /// delay before the *first* retry, i.e. the second overall attempt).
+    pub fn delay_for_attempt(&self, attempt: u32) -> Duration {
+        let exp = self.base_delay.saturating_mul(1 << attempt.min(16)).min(self.max_delay);
+        let jittered_millis = rand::thread_rng().gen_range(0..=exp.as_millis().max(1) as u64);
+        Duration::from_millis(jittered_millis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_code_from_a_clickhouse_error_body() {
+        let body = "Code: 241. DB::Exception: Memory limit (total) exceeded: ...";
+        assert_eq!(ClickHouseError::extract_code(body), Some(241));
+    }
+
+    #[test]
+    fn extract_code_returns_none_without_a_code_prefix() {
+        let body = "DB::Exception: something went wrong";
+        assert_eq!(ClickHouseError::extract_code(body), None);
+    }
+
+    #[test]
+    fn from_response_classifies_known_codes() {
+        let timeout = ClickHouseError::from_response(
+            reqwest::StatusCode::INTERNAL_SERVER_ERROR,
+            "Code: 159. DB::Exception: Timeout exceeded",
+        );
+        assert!(matches!(timeout, ClickHouseError::Timeout { .. }));
+        assert!(timeout.is_retryable());
+
+        let overloaded = ClickHouseError::from_response(
+            reqwest::StatusCode::INTERNAL_SERVER_ERROR,
+            "Code: 202. DB::Exception: Too many simultaneous queries",
+        );
+        assert!(matches!(overloaded, ClickHouseError::Overloaded { code: 202, .. }));
+        assert!(overloaded.is_retryable());
+    }
+
+    #[test]
+    fn from_response_defaults_unrecognized_5xx_to_non_retryable() {
+        let err = ClickHouseError::from_response(
+            reqwest::StatusCode::INTERNAL_SERVER_ERROR,
+            "Code: 62. DB::Exception: Syntax error",
+        );
+        assert!(
+            !err.is_retryable(),
+            "an unrecognized code shouldn't be assumed transient just because the status is 5xx"
+        );
+    }
+
+    #[test]
+    fn from_response_defaults_unclassified_body_to_non_retryable() {
+        let err = ClickHouseError::from_response(
+            reqwest::StatusCode::INTERNAL_SERVER_ERROR,
+            "not a ClickHouse-formatted error body",
+        );
+        assert!(!err.is_retryable());
+    }
+
+    #[test]
+    fn delay_for_attempt_stays_within_configured_bounds() {
+        let policy = RetryPolicy::default();
+        assert!(policy.delay_for_attempt(0) <= policy.base_delay);
+        assert!(policy.delay_for_attempt(100) <= policy.max_delay);
+    }
+}
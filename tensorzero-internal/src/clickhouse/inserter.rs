@@ -0,0 +1,224 @@
+//! A buffered, self-flushing writer for high-volume ClickHouse inserts.
+//!
+//! Loosely mirrors the inserter in loyd/clickhouse.rs: callers append typed
+//! rows with [`Inserter::write`] (a synchronous, allocation-only operation),
+//! and the buffer is flushed once any configured threshold is crossed —
+//! row count, buffered byte size, or wall-clock age of the oldest buffered
+//! row. This turns "one `INSERT` per inference/feedback event" into "one
+//! `INSERT` per few thousand events", which is what actually matters under
+//! load.
+
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use super::error::RetryPolicy;
+use super::pool::ClickHousePool;
+use super::query::validate_table_name;
+use super::Error;
+
+/// How old `clock_cache` must be before [`Inserter::should_commit`] replaces
+/// it with a fresh `Instant::now()` for the `with_period` check. Keeps the
+/// clock the period is measured against "coarse" rather than exact, while
+/// still being fine-grained enough for any `with_period` used in practice.
+const CLOCK_GRANULARITY: Duration = Duration::from_millis(50);
+
+/// A threshold-triggered, single-table batch writer for rows of type `T`.
+///
+/// `T` must serialize to the JSON shape of the destination table's columns;
+/// [`Inserter::commit`] writes the buffered rows with `INSERT INTO <table>
+/// FORMAT JSONEachRow`, matching the format the rest of the gateway already
+/// uses for ClickHouse reads and writes. The connection used for that write
+/// is checked out of `pool` for the duration of the flush and returned
+/// immediately afterwards, rather than pinned for the Inserter's lifetime.
+pub struct Inserter<T> {
+    table_name: String,
+    pool: ClickHousePool,
+    rows: Vec<T>,
+    buffered_bytes: usize,
+    oldest_row_at: Option<Instant>,
+    max_rows: Option<usize>,
+    max_bytes: Option<usize>,
+    period: Option<Duration>,
+    // Cached so the hot `write` path only calls `Instant::now()` on the
+    // first buffered row; refreshed there and by `should_commit` once the
+    // cached reading is at least `CLOCK_GRANULARITY` old.
+    clock_cache: Instant,
+}
+
+impl<T: Serialize> Inserter<T> {
+    /// Fails if `table_name` isn't on [`validate_table_name`]'s allow-list:
+    /// `table_name` ends up interpolated directly into the `INSERT` query
+    /// text in [`Inserter::commit`] (ClickHouse can't bind identifiers as
+    /// query parameters), so it's checked once up front rather than on every
+    /// flush.
+    pub fn new(pool: ClickHousePool, table_name: impl Into<String>) -> Result<Self, Error> {
+        let table_name = table_name.into();
+        validate_table_name(&table_name)?;
+        Ok(Self {
+            table_name,
+            pool,
+            rows: Vec::new(),
+            buffered_bytes: 0,
+            oldest_row_at: None,
+            max_rows: None,
+            max_bytes: None,
+            period: None,
+            clock_cache: Instant::now(),
+        })
+    }
+
+    pub fn with_max_rows(mut self, max_rows: usize) -> Self {
+        self.max_rows = Some(max_rows);
+        self
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn with_period(mut self, period: Duration) -> Self {
+        self.period = Some(period);
+        self
+    }
+
+    /// Appends `row` to the in-memory buffer and updates the row/byte
+    /// counters. This never touches the network or the OS clock beyond the
+    /// already-cached [`Instant`] set on the first buffered row, so it's
+    /// safe to call on every write path without adding latency.
+    pub fn write(&mut self, row: T) -> Result<(), Error> {
+        let serialized = serde_json::to_vec(&row)
+            .map_err(|e| Error::new(format!("Failed to serialize row for {}: {e}", self.table_name)))?;
+        if self.rows.is_empty() {
+            self.clock_cache = Instant::now();
+            self.oldest_row_at = Some(self.clock_cache);
+        }
+        self.buffered_bytes += serialized.len();
+        self.rows.push(row);
+        Ok(())
+    }
+
+    /// Returns `true` if any configured threshold has been crossed and the
+    /// buffer should be flushed. The `with_period` check compares against
+    /// the cached clock, which is replaced at most once every
+    /// [`CLOCK_GRANULARITY`]; while the period has not yet elapsed, each call
+    /// still reads the OS clock (via `Instant::elapsed`) to decide whether
+    /// the cache is old enough to replace.
+    pub fn should_commit(&mut self) -> bool {
+        if self.rows.is_empty() {
+            return false;
+        }
+        if let Some(max_rows) = self.max_rows {
+            if self.rows.len() >= max_rows {
+                return true;
+            }
+        }
+        if let Some(max_bytes) = self.max_bytes {
+            if self.buffered_bytes >= max_bytes {
+                return true;
+            }
+        }
+        if let (Some(period), Some(oldest_row_at)) = (self.period, self.oldest_row_at) {
+            if self.clock_cache.duration_since(oldest_row_at) < period
+                && self.clock_cache.elapsed() >= CLOCK_GRANULARITY
+            {
+                self.clock_cache = Instant::now();
+            }
+            if self.clock_cache.duration_since(oldest_row_at) >= period {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Flushes the buffer if, and only if, [`Inserter::should_commit`]
+    /// currently returns `true`. Callers on a hot write path should call
+    /// this after every [`Inserter::write`]; a background task can instead
+    /// poll on a timer to catch the `with_period` threshold even when writes
+    /// are infrequent.
+    pub async fn commit_if_ready(&mut self) -> Result<(), Error> {
+        if self.should_commit() {
+            self.commit().await?;
+        }
+        Ok(())
+    }
+
+    /// Unconditionally flushes whatever is currently buffered.
+    pub async fn commit(&mut self) -> Result<(), Error> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let mut body = Vec::with_capacity(self.buffered_bytes);
+        for row in &self.rows {
+            serde_json::to_writer(&mut body, row)
+                .map_err(|e| Error::new(format!("Failed to serialize row for {}: {e}", self.table_name)))?;
+            body.push(b'\n');
+        }
+        let query = format!(
+            "INSERT INTO {} FORMAT JSONEachRow\n{}",
+            self.table_name,
+            String::from_utf8_lossy(&body)
+        );
+        // Retries transient overload/timeout/transport failures with backoff,
+        // so a burst of writes surviving a momentarily overloaded ClickHouse
+        // doesn't just drop the batch on the floor.
+        let connection = self.pool.get().await?;
+        connection
+            .run_query_with_retry(query, None, &RetryPolicy::default())
+            .await?;
+        self.rows.clear();
+        self.buffered_bytes = 0;
+        self.oldest_row_at = None;
+        Ok(())
+    }
+
+    /// Flushes unconditionally, ignoring all thresholds. Intended for
+    /// shutdown paths, where any buffered rows must not be dropped on the
+    /// floor.
+    pub async fn force_commit(&mut self) -> Result<(), Error> {
+        self.commit().await
+    }
+
+    pub fn buffered_rows(&self) -> usize {
+        self.rows.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct TestRow {
+        value: u32,
+    }
+
+    fn test_pool() -> ClickHousePool {
+        ClickHousePool::new("http://localhost:8123", 1, Duration::from_secs(60))
+    }
+
+    #[test]
+    fn with_period_flips_should_commit_after_the_period_elapses() {
+        let mut inserter: Inserter<TestRow> = Inserter::new(test_pool(), "ModelInference")
+            .unwrap()
+            .with_period(Duration::from_millis(10));
+        inserter.write(TestRow { value: 1 }).unwrap();
+        assert!(
+            !inserter.should_commit(),
+            "should not commit before the period elapses"
+        );
+
+        std::thread::sleep(CLOCK_GRANULARITY + Duration::from_millis(10));
+        assert!(
+            inserter.should_commit(),
+            "should commit once the period has elapsed"
+        );
+    }
+
+    #[test]
+    fn new_rejects_a_table_name_not_on_the_allow_list() {
+        let result: Result<Inserter<TestRow>, Error> = Inserter::new(test_pool(), "TestTable");
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,159 @@
+//! Client for talking to the ClickHouse instance backing TensorZero's
+//! observability tables (inferences, model inferences, feedback, batches,
+//! ...).
+//!
+//! [`ClickHouseConnectionInfo`] is the single entry point the rest of the
+//! gateway uses to run queries; [`test_helpers`] layers typed/ad-hoc read
+//! helpers on top of it for integration tests.
+
+pub mod error;
+pub mod inserter;
+pub mod pool;
+pub mod query;
+pub mod row_binary;
+pub mod test_helpers;
+
+use std::collections::HashMap;
+
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use url::Url;
+
+pub use error::ClickHouseError as Error;
+use error::RetryPolicy;
+use row_binary::RowBinaryReader;
+
+/// A connection to a single ClickHouse instance, addressed over HTTP.
+#[derive(Debug, Clone)]
+pub struct ClickHouseConnectionInfo {
+    base_url: Url,
+    client: Client,
+}
+
+impl ClickHouseConnectionInfo {
+    pub async fn new(clickhouse_url: &str) -> Result<Self, Error> {
+        let base_url =
+            Url::parse(clickhouse_url).map_err(|e| Error::new(format!("Invalid ClickHouse URL: {e}")))?;
+        Ok(Self {
+            base_url,
+            client: Client::new(),
+        })
+    }
+
+    /// Runs `query` against ClickHouse and returns the raw response body as a
+    /// `String`. Callers that use `FORMAT JSONEachRow` get back newline-
+    /// delimited JSON; see [`ClickHouseConnectionInfo::run_query_typed`] for a
+    /// typed alternative built on `FORMAT RowBinaryWithNamesAndTypes`.
+    pub async fn run_query_synchronous(
+        &self,
+        query: String,
+        params: Option<&HashMap<&str, &str>>,
+    ) -> Result<String, Error> {
+        let mut request = self.client.post(self.base_url.clone()).body(query);
+        if let Some(params) = params {
+            for (name, value) in params {
+                request = request.query(&[(format!("param_{name}"), value)]);
+            }
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Error::new(format!("Failed to send query to ClickHouse: {e}")))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(Error::from_response(status, &body));
+        }
+        response
+            .text()
+            .await
+            .map_err(|e| Error::new(format!("Failed to read ClickHouse response: {e}")))
+    }
+
+    /// Runs `query` via [`ClickHouseConnectionInfo::run_query_synchronous`],
+    /// retrying with exponential backoff and jitter when the failure is one
+    /// [`ClickHouseError::is_retryable`] reports as transient (overload,
+    /// timeout, transport). A query ClickHouse rejects outright (bad syntax,
+    /// type mismatch, ...) fails immediately on the first attempt, since
+    /// retrying it would just waste the backoff budget on a query that can
+    /// never succeed.
+    pub async fn run_query_with_retry(
+        &self,
+        query: String,
+        params: Option<&HashMap<&str, &str>>,
+        retry_policy: &RetryPolicy,
+    ) -> Result<String, Error> {
+        let mut attempt = 0;
+        loop {
+            match self.run_query_synchronous(query.clone(), params).await {
+                Ok(text) => return Ok(text),
+                Err(e) if e.is_retryable() && attempt + 1 < retry_policy.max_attempts => {
+                    let delay = retry_policy.delay_for_attempt(attempt);
+                    tracing::warn!(
+                        "ClickHouse query failed (attempt {}/{}), retrying in {:?}: {e}",
+                        attempt + 1,
+                        retry_policy.max_attempts,
+                        delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Like [`ClickHouseConnectionInfo::run_query_synchronous`], but appends
+    /// `FORMAT RowBinaryWithNamesAndTypes` to `query` and decodes the
+    /// response into `Vec<T>` without going through a JSON text `String`.
+    /// Each row is not decoded straight into `T`: it is first built as an
+    /// intermediate `serde_json::Value` object (see
+    /// [`RowBinaryReader::read_rows`]) and then deserialized into `T`.
+    pub async fn run_query_typed<T: DeserializeOwned>(
+        &self,
+        query: String,
+        params: Option<&HashMap<&str, &str>>,
+    ) -> Result<Vec<T>, Error> {
+        let mut request = self
+            .client
+            .post(self.base_url.clone())
+            .body(format!("{query} FORMAT RowBinaryWithNamesAndTypes"));
+        if let Some(params) = params {
+            for (name, value) in params {
+                request = request.query(&[(format!("param_{name}"), value)]);
+            }
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Error::new(format!("Failed to send query to ClickHouse: {e}")))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(Error::from_response(status, &body));
+        }
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| Error::new(format!("Failed to read ClickHouse response: {e}")))?;
+        let mut reader = RowBinaryReader::new(&bytes);
+        let columns = reader
+            .read_header()
+            .map_err(|e| Error::new(format!("Failed to decode RowBinary header: {e}")))?;
+        reader
+            .read_rows(&columns)
+            .map_err(|e| Error::new(format!("Failed to decode RowBinary rows: {e}")))
+    }
+}
+
+/// Escapes a user-controlled string for safe embedding in a ClickHouse
+/// string literal used only for *test assertions* (e.g. comparing a bound
+/// parameter value against what ended up in a table). Production code paths
+/// should bind parameters instead; see [`select_human_static_evaluation_feedback_clickhouse`]
+/// in `test_helpers` for the pattern.
+///
+/// [`select_human_static_evaluation_feedback_clickhouse`]: test_helpers::select_human_static_evaluation_feedback_clickhouse
+#[cfg(feature = "e2e_tests")]
+pub fn escape_string_for_clickhouse_comparison(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\'', "\\'")
+}
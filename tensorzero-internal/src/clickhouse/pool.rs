@@ -0,0 +1,155 @@
+//! A small async connection pool for [`ClickHouseConnectionInfo`], modeled
+//! after deadpool-postgres: a bounded set of connections handed out via
+//! [`ClickHousePool::get`] and returned to the idle list automatically when
+//! the returned guard drops.
+//!
+//! Idle connections are health-checked with a `SELECT 1` before being
+//! reused, so a keep-alive socket ClickHouse (or an intermediate load
+//! balancer) has silently closed surfaces as a fresh connection on the next
+//! `get()` instead of as a confusing query error on the caller's next query.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::{ClickHouseConnectionInfo, Error};
+
+struct IdleConnection {
+    connection: ClickHouseConnectionInfo,
+    idle_since: Instant,
+}
+
+struct Inner {
+    clickhouse_url: String,
+    idle: Mutex<VecDeque<IdleConnection>>,
+    semaphore: Arc<Semaphore>,
+    idle_timeout: Duration,
+}
+
+/// A bounded pool of [`ClickHouseConnectionInfo`] connections.
+#[derive(Clone)]
+pub struct ClickHousePool {
+    inner: Arc<Inner>,
+}
+
+impl ClickHousePool {
+    pub fn new(clickhouse_url: impl Into<String>, max_size: usize, idle_timeout: Duration) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                clickhouse_url: clickhouse_url.into(),
+                idle: Mutex::new(VecDeque::new()),
+                semaphore: Arc::new(Semaphore::new(max_size)),
+                idle_timeout,
+            }),
+        }
+    }
+
+    /// Checks out a connection, blocking until `max_size` concurrent
+    /// connections are no longer in use. Idle connections are reused after
+    /// passing a health check; everything else (first use, expired idle
+    /// connections, failed health checks) falls back to opening a fresh
+    /// connection.
+    pub async fn get(&self) -> Result<PooledConnection, Error> {
+        let permit = Arc::clone(&self.inner.semaphore)
+            .acquire_owned()
+            .await
+            .map_err(|_| Error::new("ClickHouse connection pool has been closed"))?;
+
+        loop {
+            let candidate = self.inner.idle.lock().unwrap().pop_front();
+            let Some(candidate) = candidate else {
+                let connection = ClickHouseConnectionInfo::new(&self.inner.clickhouse_url).await?;
+                return Ok(PooledConnection {
+                    connection: Some(connection),
+                    inner: Arc::clone(&self.inner),
+                    permit: Some(permit),
+                });
+            };
+            if candidate.idle_since.elapsed() > self.inner.idle_timeout {
+                continue;
+            }
+            if Self::ping(&candidate.connection).await {
+                return Ok(PooledConnection {
+                    connection: Some(candidate.connection),
+                    inner: Arc::clone(&self.inner),
+                    permit: Some(permit),
+                });
+            }
+            // Health check failed (e.g. a stale keep-alive socket); drop this
+            // one and keep looking.
+        }
+    }
+
+    async fn ping(connection: &ClickHouseConnectionInfo) -> bool {
+        connection
+            .run_query_synchronous("SELECT 1".to_string(), None)
+            .await
+            .is_ok()
+    }
+}
+
+/// A connection checked out of a [`ClickHousePool`]. Derefs to
+/// [`ClickHouseConnectionInfo`]; returned to the pool's idle list when
+/// dropped, so the next [`ClickHousePool::get`] can reuse it.
+pub struct PooledConnection {
+    connection: Option<ClickHouseConnectionInfo>,
+    inner: Arc<Inner>,
+    // Held only to release the pool's capacity slot on drop.
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl std::ops::Deref for PooledConnection {
+    type Target = ClickHouseConnectionInfo;
+
+    fn deref(&self) -> &Self::Target {
+        self.connection
+            .as_ref()
+            .expect("PooledConnection's connection is only taken on drop")
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            self.inner.idle.lock().unwrap().push_back(IdleConnection {
+                connection,
+                idle_since: Instant::now(),
+            });
+        }
+        // Dropping `self.permit` releases the capacity slot back to the semaphore.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn get_creates_a_fresh_connection_when_idle_is_empty() {
+        let pool = ClickHousePool::new("http://fresh.invalid", 4, Duration::from_secs(60));
+        let connection = pool.get().await.unwrap();
+        assert_eq!(connection.base_url.as_str(), "http://fresh.invalid/");
+    }
+
+    #[tokio::test]
+    async fn expired_idle_connections_are_not_reused() {
+        let pool = ClickHousePool::new("http://fresh.invalid", 4, Duration::from_millis(10));
+        let stale_connection = ClickHouseConnectionInfo::new("http://stale.invalid").await.unwrap();
+        pool.inner.idle.lock().unwrap().push_back(IdleConnection {
+            connection: stale_connection,
+            // Already well past `idle_timeout`, so `get` must skip it rather
+            // than health-check (and reuse) it.
+            idle_since: Instant::now() - Duration::from_secs(1),
+        });
+
+        let connection = pool.get().await.unwrap();
+        assert_eq!(
+            connection.base_url.as_str(),
+            "http://fresh.invalid/",
+            "an expired idle connection should be dropped, not handed back out"
+        );
+        assert!(pool.inner.idle.lock().unwrap().is_empty());
+    }
+}
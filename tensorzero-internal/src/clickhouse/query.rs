@@ -0,0 +1,126 @@
+//! A parameterized query builder for [`ClickHouseConnectionInfo`].
+//!
+//! `select_human_static_evaluation_feedback_clickhouse` in `test_helpers`
+//! was the only caller using ClickHouse's `{name:Type}` bind-parameter
+//! syntax instead of `format!`-interpolating values straight into the query
+//! text; everything else in that module built SQL the unsafe way. [`Query`]
+//! promotes the bind-parameter pattern into the default way to build a
+//! query: values are bound with [`Query::bind`] and sent as HTTP
+//! `param_<name>` parameters, so a value can never break out of the literal
+//! it's bound into.
+//!
+//! Table and column names can't be bound this way (ClickHouse's parameters
+//! only cover values, not identifiers), so [`validate_table_name`] gives
+//! call sites that must interpolate a table name a closed allow-list to
+//! check it against first.
+
+use std::collections::HashMap;
+
+use serde::de::DeserializeOwned;
+use uuid::Uuid;
+
+use super::{ClickHouseConnectionInfo, Error};
+
+/// A ClickHouse SQL string together with its bound parameters, built
+/// incrementally via [`Query::bind`].
+pub struct Query<'a> {
+    sql: String,
+    params: HashMap<&'a str, String>,
+}
+
+impl<'a> Query<'a> {
+    pub fn new(sql: impl Into<String>) -> Self {
+        Self {
+            sql: sql.into(),
+            params: HashMap::new(),
+        }
+    }
+
+    /// Binds `name` to `value`. `name` must match a `{name:Type}` placeholder
+    /// already present in the SQL passed to [`Query::new`]; `value` is sent
+    /// as the `param_<name>` HTTP parameter rather than interpolated into
+    /// the query text.
+    pub fn bind(mut self, name: &'a str, value: impl ToBoundValue) -> Self {
+        self.params.insert(name, value.to_bound_value());
+        self
+    }
+
+    fn param_map(&self) -> HashMap<&str, &str> {
+        self.params.iter().map(|(k, v)| (*k, v.as_str())).collect()
+    }
+
+    pub async fn run(&self, connection: &ClickHouseConnectionInfo) -> Result<String, Error> {
+        let params = self.param_map();
+        connection
+            .run_query_synchronous(self.sql.clone(), Some(&params))
+            .await
+    }
+
+    pub async fn run_typed<T: DeserializeOwned>(
+        &self,
+        connection: &ClickHouseConnectionInfo,
+    ) -> Result<Vec<T>, Error> {
+        let params = self.param_map();
+        connection.run_query_typed(self.sql.clone(), Some(&params)).await
+    }
+}
+
+/// A value that can be bound into a [`Query`]. Implemented for the handful
+/// of types that actually get bound throughout the codebase; add a new impl
+/// rather than formatting a value to a `String` at the call site, so binding
+/// stays centralized in one place.
+pub trait ToBoundValue {
+    fn to_bound_value(&self) -> String;
+}
+
+impl ToBoundValue for Uuid {
+    fn to_bound_value(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl ToBoundValue for &str {
+    fn to_bound_value(&self) -> String {
+        (*self).to_string()
+    }
+}
+
+impl ToBoundValue for String {
+    fn to_bound_value(&self) -> String {
+        self.clone()
+    }
+}
+
+/// Table names that are allowed to be interpolated directly into query text.
+/// ClickHouse's bind parameters only cover values, not identifiers, so a
+/// caller that needs a dynamic table name (e.g. selecting from whichever
+/// feedback table a test is exercising) must validate it against this list
+/// with [`validate_table_name`] rather than trusting it outright.
+const ALLOWED_TABLE_NAMES: &[&str] = &[
+    "ChatInference",
+    "JsonInference",
+    "ModelInference",
+    "ChatInferenceDatapoint",
+    "JsonInferenceDatapoint",
+    "InferenceTag",
+    "BatchModelInference",
+    "BatchIdByInferenceId",
+    "BatchRequest",
+    "FeedbackTag",
+    "StaticEvaluationHumanFeedback",
+    "BooleanMetricFeedback",
+    "FloatMetricFeedback",
+    "CommentFeedback",
+    "DemonstrationFeedback",
+];
+
+/// Validates that `table_name` is a recognized table, for the identifiers
+/// that genuinely must be interpolated into query text rather than bound as
+/// a [`Query`] parameter.
+pub fn validate_table_name(table_name: &str) -> Result<&str, Error> {
+    ALLOWED_TABLE_NAMES
+        .iter()
+        .find(|&&allowed| allowed == table_name)
+        .copied()
+        .ok_or_else(|| Error::new(format!("`{table_name}` is not a recognized ClickHouse table name")))
+}
@@ -0,0 +1,211 @@
+//! Decoding for ClickHouse's `RowBinaryWithNamesAndTypes` wire format.
+//!
+//! This is a minimal reader, not a general-purpose ClickHouse client: it only
+//! understands the handful of column types TensorZero actually writes
+//! (strings, the fixed-width integers/floats, `UUID`, `DateTime64`, and
+//! `Nullable(T)` wrappers around those). Anything else surfaces as a
+//! [`RowBinaryError::UnsupportedType`] rather than silently misreading bytes.
+//!
+//! The wire format, for reference:
+//!   - header: LEB128 column count, then for each column a LEB128-prefixed
+//!     name string, then (in a second pass) a LEB128-prefixed type string
+//!   - each row: one value per column, back to back, in column order
+//!   - `Nullable(T)` values are preceded by a single presence byte (`1` means
+//!     NULL, `0` means the `T` payload follows)
+
+use serde::de::DeserializeOwned;
+use uuid::Uuid;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RowBinaryError {
+    #[error("unexpected end of RowBinary stream")]
+    UnexpectedEof,
+    #[error("unsupported ClickHouse type in RowBinary stream: {0}")]
+    UnsupportedType(String),
+    #[error("RowBinary value did not match the expected row shape: {0}")]
+    Shape(String),
+}
+
+/// A single cursor over a `FORMAT RowBinaryWithNamesAndTypes` response body.
+///
+/// Construct with [`RowBinaryReader::new`], consume the header once with
+/// [`RowBinaryReader::read_header`], then call [`RowBinaryReader::read_rows`]
+/// to decode every row into `T` via an intermediate `serde_json::Value`, so
+/// that callers can reuse the same `#[derive(Deserialize)]` structs they'd
+/// use for `FORMAT JSONEachRow`.
+pub struct RowBinaryReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> RowBinaryReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn read_byte(&mut self) -> Result<u8, RowBinaryError> {
+        let byte = *self.bytes.get(self.pos).ok_or(RowBinaryError::UnexpectedEof)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn read_exact(&mut self, len: usize) -> Result<&'a [u8], RowBinaryError> {
+        let end = self.pos.checked_add(len).ok_or(RowBinaryError::UnexpectedEof)?;
+        let slice = self.bytes.get(self.pos..end).ok_or(RowBinaryError::UnexpectedEof)?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    /// Reads a ClickHouse LEB128 varint (used for string lengths and the
+    /// leading column count).
+    fn read_varint(&mut self) -> Result<u64, RowBinaryError> {
+        let mut result: u64 = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_byte()?;
+            result |= u64::from(byte & 0x7F) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+            shift += 7;
+        }
+    }
+
+    fn read_string(&mut self) -> Result<String, RowBinaryError> {
+        let len = self.read_varint()? as usize;
+        let bytes = self.read_exact(len)?;
+        Ok(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    /// Reads the `names, types` header that precedes the row data in
+    /// `RowBinaryWithNamesAndTypes`, returning the column names paired with
+    /// their declared ClickHouse type.
+    pub fn read_header(&mut self) -> Result<Vec<(String, String)>, RowBinaryError> {
+        let num_columns = self.read_varint()? as usize;
+        let mut names = Vec::with_capacity(num_columns);
+        for _ in 0..num_columns {
+            names.push(self.read_string()?);
+        }
+        let mut columns = Vec::with_capacity(num_columns);
+        for name in names {
+            let ty = self.read_string()?;
+            columns.push((name, ty));
+        }
+        Ok(columns)
+    }
+
+    fn read_value(&mut self, ty: &str) -> Result<serde_json::Value, RowBinaryError> {
+        if let Some(inner) = ty.strip_prefix("Nullable(").and_then(|s| s.strip_suffix(')')) {
+            return if self.read_byte()? == 1 {
+                Ok(serde_json::Value::Null)
+            } else {
+                self.read_value(inner)
+            };
+        }
+        match ty {
+            "String" => Ok(serde_json::Value::String(self.read_string()?)),
+            "UUID" => {
+                // Two little-endian u64 halves, the half holding the UUID's
+                // first eight textual bytes first, each itself byte-swapped
+                // relative to the UUID's textual byte order.
+                let low = u64::from_le_bytes(self.read_exact(8)?.try_into().unwrap());
+                let high = u64::from_le_bytes(self.read_exact(8)?.try_into().unwrap());
+                let mut bytes = [0u8; 16];
+                bytes[0..8].copy_from_slice(&low.to_be_bytes());
+                bytes[8..16].copy_from_slice(&high.to_be_bytes());
+                Ok(serde_json::Value::String(Uuid::from_bytes(bytes).to_string()))
+            }
+            "Int8" => Ok(serde_json::Value::from(self.read_byte()? as i8)),
+            "UInt8" => Ok(serde_json::Value::from(self.read_byte()?)),
+            "Bool" => Ok(serde_json::Value::Bool(self.read_byte()? != 0)),
+            "Int16" => Ok(serde_json::Value::from(i16::from_le_bytes(
+                self.read_exact(2)?.try_into().unwrap(),
+            ))),
+            "UInt16" => Ok(serde_json::Value::from(u16::from_le_bytes(
+                self.read_exact(2)?.try_into().unwrap(),
+            ))),
+            "Int32" => Ok(serde_json::Value::from(i32::from_le_bytes(
+                self.read_exact(4)?.try_into().unwrap(),
+            ))),
+            "UInt32" => Ok(serde_json::Value::from(u32::from_le_bytes(
+                self.read_exact(4)?.try_into().unwrap(),
+            ))),
+            "Int64" | "DateTime64" => Ok(serde_json::Value::from(i64::from_le_bytes(
+                self.read_exact(8)?.try_into().unwrap(),
+            ))),
+            "UInt64" => Ok(serde_json::Value::from(u64::from_le_bytes(
+                self.read_exact(8)?.try_into().unwrap(),
+            ))),
+            "Float32" => Ok(serde_json::Value::from(f32::from_le_bytes(
+                self.read_exact(4)?.try_into().unwrap(),
+            ))),
+            "Float64" => Ok(serde_json::Value::from(f64::from_le_bytes(
+                self.read_exact(8)?.try_into().unwrap(),
+            ))),
+            // ClickHouse names the type with its precision (and optional
+            // timezone) in the header, e.g. `DateTime64(3)`.
+            other if other.starts_with("DateTime64(") => Ok(serde_json::Value::from(
+                i64::from_le_bytes(self.read_exact(8)?.try_into().unwrap()),
+            )),
+            other => Err(RowBinaryError::UnsupportedType(other.to_string())),
+        }
+    }
+
+    /// Decodes every remaining row into `T`, assuming [`read_header`] has
+    /// already been called on this reader.
+    ///
+    /// [`read_header`]: RowBinaryReader::read_header
+    pub fn read_rows<T: DeserializeOwned>(
+        &mut self,
+        columns: &[(String, String)],
+    ) -> Result<Vec<T>, RowBinaryError> {
+        let mut rows = Vec::new();
+        while self.pos < self.bytes.len() {
+            let mut row = serde_json::Map::with_capacity(columns.len());
+            for (name, ty) in columns {
+                row.insert(name.clone(), self.read_value(ty)?);
+            }
+            let row = serde_json::from_value(serde_json::Value::Object(row))
+                .map_err(|e| RowBinaryError::Shape(e.to_string()))?;
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_uuid_round_trip() {
+        let uuid = Uuid::parse_str("00010203-0405-0607-0809-0a0b0c0d0e0f").unwrap();
+        let textual_bytes = uuid.as_bytes();
+
+        // Each 8-byte half of the UUID is written to the wire byte-reversed,
+        // the half holding the first eight textual bytes first, matching what `read_value`'s `"UUID"` arm expects.
+        let mut wire = Vec::with_capacity(16);
+        wire.extend(textual_bytes[0..8].iter().rev());
+        wire.extend(textual_bytes[8..16].iter().rev());
+
+        let mut reader = RowBinaryReader::new(&wire);
+        let decoded = reader.read_value("UUID").unwrap();
+        assert_eq!(decoded, serde_json::Value::String(uuid.to_string()));
+    }
+
+    #[test]
+    fn decodes_bool_as_a_json_bool_not_a_number() {
+        let wire = [1u8];
+        let mut reader = RowBinaryReader::new(&wire);
+        let decoded = reader.read_value("Bool").unwrap();
+        assert_eq!(decoded, serde_json::Value::Bool(true));
+    }
+
+    #[test]
+    fn decodes_uint8_as_a_json_number_not_a_bool() {
+        let wire = [1u8];
+        let mut reader = RowBinaryReader::new(&wire);
+        let decoded = reader.read_value("UInt8").unwrap();
+        assert_eq!(decoded, serde_json::Value::from(1u8));
+    }
+}
@@ -1,22 +1,30 @@
 #![allow(clippy::unwrap_used, clippy::expect_used, clippy::print_stdout)]
 #[cfg(feature = "e2e_tests")]
 use super::escape_string_for_clickhouse_comparison;
+use super::pool::{ClickHousePool, PooledConnection};
+use super::query::Query;
+#[cfg(feature = "e2e_tests")]
+use super::query::validate_table_name;
 use super::ClickHouseConnectionInfo;
 use serde::Deserialize;
 use serde_json::Value;
-#[cfg(feature = "e2e_tests")]
-use std::collections::HashMap;
+use std::time::Duration;
 use uuid::Uuid;
 
 lazy_static::lazy_static! {
     pub static ref CLICKHOUSE_URL: String = std::env::var("TENSORZERO_CLICKHOUSE_URL").expect("Environment variable TENSORZERO_CLICKHOUSE_URL must be set");
+    // Shared across every test in the process, so a full e2e run actually
+    // reuses connections instead of opening (and health-checking) a brand
+    // new one for every single selector call.
+    static ref CLICKHOUSE_POOL: ClickHousePool =
+        ClickHousePool::new(CLICKHOUSE_URL.clone(), 16, Duration::from_secs(60));
 }
 
-pub async fn get_clickhouse() -> ClickHouseConnectionInfo {
-    let clickhouse_url = url::Url::parse(&CLICKHOUSE_URL).unwrap();
+pub async fn get_clickhouse() -> PooledConnection {
     let start = std::time::Instant::now();
     println!("Connecting to ClickHouse");
-    let res = ClickHouseConnectionInfo::new(clickhouse_url.as_ref())
+    let res = CLICKHOUSE_POOL
+        .get()
         .await
         .expect("Failed to connect to ClickHouse");
     println!("Connected to ClickHouse in {:?}", start.elapsed());
@@ -41,13 +49,9 @@ pub async fn select_chat_datapoint_clickhouse(
     #[cfg(feature = "e2e_tests")]
     clickhouse_flush_async_insert(clickhouse_connection_info).await;
 
-    let query = format!(
-        "SELECT * FROM ChatInferenceDatapoint WHERE id = '{}' LIMIT 1 FORMAT JSONEachRow",
-        inference_id
-    );
-
-    let text = clickhouse_connection_info
-        .run_query_synchronous(query, None)
+    let text = Query::new("SELECT * FROM ChatInferenceDatapoint WHERE id = {id:UUID} LIMIT 1 FORMAT JSONEachRow")
+        .bind("id", inference_id)
+        .run(clickhouse_connection_info)
         .await
         .unwrap();
     let json: Value = serde_json::from_str(&text).ok()?;
@@ -62,13 +66,9 @@ pub async fn select_json_datapoint_clickhouse(
     #[cfg(feature = "e2e_tests")]
     clickhouse_flush_async_insert(clickhouse_connection_info).await;
 
-    let query = format!(
-        "SELECT * FROM JsonInferenceDatapoint WHERE id = '{}' LIMIT 1 FORMAT JSONEachRow",
-        inference_id
-    );
-
-    let text = clickhouse_connection_info
-        .run_query_synchronous(query, None)
+    let text = Query::new("SELECT * FROM JsonInferenceDatapoint WHERE id = {id:UUID} LIMIT 1 FORMAT JSONEachRow")
+        .bind("id", inference_id)
+        .run(clickhouse_connection_info)
         .await
         .unwrap();
     let json: Value = serde_json::from_str(&text).ok()?;
@@ -82,13 +82,9 @@ pub async fn select_chat_inference_clickhouse(
     #[cfg(feature = "e2e_tests")]
     clickhouse_flush_async_insert(clickhouse_connection_info).await;
 
-    let query = format!(
-        "SELECT * FROM ChatInference WHERE id = '{}' LIMIT 1 FORMAT JSONEachRow",
-        inference_id
-    );
-
-    let text = clickhouse_connection_info
-        .run_query_synchronous(query, None)
+    let text = Query::new("SELECT * FROM ChatInference WHERE id = {id:UUID} LIMIT 1 FORMAT JSONEachRow")
+        .bind("id", inference_id)
+        .run(clickhouse_connection_info)
         .await
         .unwrap();
     let json: Value = serde_json::from_str(&text).ok()?;
@@ -103,13 +99,9 @@ pub async fn select_json_inference_clickhouse(
     clickhouse_flush_async_insert(clickhouse_connection_info).await;
 
     // We limit to 1 in case there are duplicate entries (can be caused by a race condition in polling batch inferences)
-    let query = format!(
-        "SELECT * FROM JsonInference WHERE id = '{}' LIMIT 1 FORMAT JSONEachRow",
-        inference_id
-    );
-
-    let text = clickhouse_connection_info
-        .run_query_synchronous(query, None)
+    let text = Query::new("SELECT * FROM JsonInference WHERE id = {id:UUID} LIMIT 1 FORMAT JSONEachRow")
+        .bind("id", inference_id)
+        .run(clickhouse_connection_info)
         .await
         .unwrap();
     let json: Value = serde_json::from_str(&text).ok()?;
@@ -124,15 +116,13 @@ pub async fn select_model_inference_clickhouse(
     clickhouse_flush_async_insert(clickhouse_connection_info).await;
 
     // We limit to 1 in case there are duplicate entries (can be caused by a race condition in polling batch inferences)
-    let query = format!(
-        "SELECT * FROM ModelInference WHERE inference_id = '{}' LIMIT 1 FORMAT JSONEachRow",
-        inference_id
-    );
-
-    let text = clickhouse_connection_info
-        .run_query_synchronous(query, None)
-        .await
-        .unwrap();
+    let text = Query::new(
+        "SELECT * FROM ModelInference WHERE inference_id = {inference_id:UUID} LIMIT 1 FORMAT JSONEachRow",
+    )
+    .bind("inference_id", inference_id)
+    .run(clickhouse_connection_info)
+    .await
+    .unwrap();
     let json: Value = serde_json::from_str(&text).ok()?;
     Some(json)
 }
@@ -145,13 +135,9 @@ pub async fn select_model_inferences_clickhouse(
     clickhouse_flush_async_insert(clickhouse_connection_info).await;
 
     // We limit to 1 in case there are duplicate entries (can be caused by a race condition in polling batch inferences)
-    let query = format!(
-        "SELECT * FROM ModelInference WHERE inference_id = '{}' FORMAT JSONEachRow",
-        inference_id
-    );
-
-    let text = clickhouse_connection_info
-        .run_query_synchronous(query, None)
+    let text = Query::new("SELECT * FROM ModelInference WHERE inference_id = {inference_id:UUID} FORMAT JSONEachRow")
+        .bind("inference_id", inference_id)
+        .run(clickhouse_connection_info)
         .await
         .unwrap();
     let json_rows: Vec<Value> = text
@@ -176,15 +162,17 @@ pub async fn select_inference_tags_clickhouse(
     #[cfg(feature = "e2e_tests")]
     clickhouse_flush_async_insert(clickhouse_connection_info).await;
 
-    let query = format!(
-        "SELECT * FROM InferenceTag WHERE function_name = '{}' AND key = '{}' AND value = '{}' AND inference_id = '{}' FORMAT JSONEachRow",
-        function_name, tag_key, tag_value, inference_id
-    );
-
-    let text = clickhouse_connection_info
-        .run_query_synchronous(query, None)
-        .await
-        .unwrap();
+    let text = Query::new(
+        "SELECT * FROM InferenceTag WHERE function_name = {function_name:String} AND key = {tag_key:String} \
+         AND value = {tag_value:String} AND inference_id = {inference_id:UUID} FORMAT JSONEachRow",
+    )
+    .bind("function_name", function_name)
+    .bind("tag_key", tag_key)
+    .bind("tag_value", tag_value)
+    .bind("inference_id", inference_id)
+    .run(clickhouse_connection_info)
+    .await
+    .unwrap();
     let json: Value = serde_json::from_str(&text).ok()?;
     Some(json)
 }
@@ -193,20 +181,18 @@ pub async fn select_batch_model_inference_clickhouse(
     clickhouse_connection_info: &ClickHouseConnectionInfo,
     inference_id: Uuid,
 ) -> Option<Value> {
-    let query = format!(
+    let text = Query::new(
         r#"
         SELECT bmi.*
         FROM BatchModelInference bmi
         INNER JOIN BatchIdByInferenceId bid ON bmi.inference_id = bid.inference_id
-        WHERE bid.inference_id = '{}'
+        WHERE bid.inference_id = {inference_id:UUID}
         FORMAT JSONEachRow"#,
-        inference_id
-    );
-
-    let text = clickhouse_connection_info
-        .run_query_synchronous(query, None)
-        .await
-        .unwrap();
+    )
+    .bind("inference_id", inference_id)
+    .run(clickhouse_connection_info)
+    .await
+    .unwrap();
     Some(serde_json::from_str(&text).unwrap())
 }
 
@@ -214,19 +200,17 @@ pub async fn select_batch_model_inferences_clickhouse(
     clickhouse_connection_info: &ClickHouseConnectionInfo,
     batch_id: Uuid,
 ) -> Option<Vec<Value>> {
-    let query = format!(
+    let text = Query::new(
         r#"
         SELECT bmi.*
         FROM BatchModelInference bmi
-        WHERE bmi.batch_id = '{}'
+        WHERE bmi.batch_id = {batch_id:UUID}
         FORMAT JSONEachRow"#,
-        batch_id
-    );
-
-    let text = clickhouse_connection_info
-        .run_query_synchronous(query, None)
-        .await
-        .unwrap();
+    )
+    .bind("batch_id", batch_id)
+    .run(clickhouse_connection_info)
+    .await
+    .unwrap();
     let json_rows: Vec<Value> = text
         .lines()
         .filter_map(|line| serde_json::from_str(line).ok())
@@ -239,15 +223,13 @@ pub async fn select_latest_batch_request_clickhouse(
     clickhouse_connection_info: &ClickHouseConnectionInfo,
     batch_id: Uuid,
 ) -> Option<Value> {
-    let query = format!(
-        "SELECT * FROM BatchRequest WHERE batch_id = '{}' ORDER BY timestamp DESC LIMIT 1 FORMAT JSONEachRow",
-        batch_id
-    );
-
-    let text = clickhouse_connection_info
-        .run_query_synchronous(query, None)
-        .await
-        .unwrap();
+    let text = Query::new(
+        "SELECT * FROM BatchRequest WHERE batch_id = {batch_id:UUID} ORDER BY timestamp DESC LIMIT 1 FORMAT JSONEachRow",
+    )
+    .bind("batch_id", batch_id)
+    .run(clickhouse_connection_info)
+    .await
+    .unwrap();
     let json: Value = serde_json::from_str(&text).ok()?;
     Some(json)
 }
@@ -260,15 +242,17 @@ pub async fn select_feedback_clickhouse(
 ) -> Option<Value> {
     clickhouse_flush_async_insert(clickhouse_connection_info).await;
 
-    let query = format!(
-        "SELECT * FROM {} WHERE id = '{}' FORMAT JSONEachRow",
-        table_name, feedback_id
-    );
-
-    let text = clickhouse_connection_info
-        .run_query_synchronous(query, None)
-        .await
-        .unwrap();
+    // `table_name` can't be bound as a query parameter (ClickHouse's bind
+    // parameters only cover values), so it's validated against an allow-list
+    // and interpolated directly instead.
+    let table_name = validate_table_name(table_name).unwrap();
+    let text = Query::new(format!(
+        "SELECT * FROM {table_name} WHERE id = {{id:UUID}} FORMAT JSONEachRow"
+    ))
+    .bind("id", feedback_id)
+    .run(clickhouse_connection_info)
+    .await
+    .unwrap();
     let json: Value = serde_json::from_str(&text).ok()?;
     Some(json)
 }
@@ -280,23 +264,19 @@ pub async fn select_feedback_by_target_id_clickhouse(
     target_id: Uuid,
     metric_name: Option<&str>,
 ) -> Option<Value> {
-    let query = match metric_name {
-        Some(metric_name) => {
-            format!(
-                "SELECT * FROM {} WHERE target_id = '{}' AND metric_name = '{}' FORMAT JSONEachRow",
-                table_name, target_id, metric_name
-            )
-        }
-        None => format!(
-            "SELECT * FROM {} WHERE target_id = '{}' FORMAT JSONEachRow",
-            table_name, target_id
-        ),
+    let table_name = validate_table_name(table_name).unwrap();
+    let mut query = match metric_name {
+        Some(metric_name) => Query::new(format!(
+            "SELECT * FROM {table_name} WHERE target_id = {{target_id:UUID}} AND metric_name = {{metric_name:String}} FORMAT JSONEachRow"
+        ))
+        .bind("metric_name", metric_name),
+        None => Query::new(format!(
+            "SELECT * FROM {table_name} WHERE target_id = {{target_id:UUID}} FORMAT JSONEachRow"
+        )),
     };
+    query = query.bind("target_id", target_id);
 
-    let text = clickhouse_connection_info
-        .run_query_synchronous(query, None)
-        .await
-        .unwrap();
+    let text = query.run(clickhouse_connection_info).await.unwrap();
     let json: Value = serde_json::from_str(&text).ok()?;
     Some(json)
 }
@@ -306,7 +286,8 @@ pub async fn stale_datapoint_clickhouse(
     clickhouse_connection_info: &ClickHouseConnectionInfo,
     datapoint_id: Uuid,
 ) {
-    let query = format!(
+    // Execute the query and ignore errors (in case the datapoint doesn't exist in this table)
+    let _ = Query::new(
         "INSERT INTO ChatInferenceDatapoint
         (
             dataset_name,
@@ -338,16 +319,13 @@ pub async fn stale_datapoint_clickhouse(
             now64() as staled_at,
             now64() as updated_at
         FROM ChatInferenceDatapoint FINAL
-        WHERE id = '{}'",
-        datapoint_id
-    );
+        WHERE id = {id:UUID}",
+    )
+    .bind("id", datapoint_id)
+    .run(clickhouse_connection_info)
+    .await;
 
-    // Execute the query and ignore errors (in case the datapoint doesn't exist in this table)
-    let _ = clickhouse_connection_info
-        .run_query_synchronous(query, None)
-        .await;
-
-    let query = format!(
+    let query = Query::new(
         "INSERT INTO JsonInferenceDatapoint
         (
             dataset_name,
@@ -379,15 +357,13 @@ pub async fn stale_datapoint_clickhouse(
             now64() as staled_at,
             now64() as updated_at
         FROM JsonInferenceDatapoint FINAL
-        WHERE id = '{}'",
-        datapoint_id
-    );
+        WHERE id = {id:UUID}",
+    )
+    .bind("id", datapoint_id);
 
     clickhouse_flush_async_insert(clickhouse_connection_info).await;
 
-    let _ = clickhouse_connection_info
-        .run_query_synchronous(query, None)
-        .await;
+    let _ = query.run(clickhouse_connection_info).await;
 }
 
 #[cfg(feature = "e2e_tests")]
@@ -399,15 +375,16 @@ pub async fn select_feedback_tags_clickhouse(
 ) -> Option<Value> {
     clickhouse_flush_async_insert(clickhouse_connection_info).await;
 
-    let query = format!(
-            "SELECT * FROM FeedbackTag WHERE metric_name = '{}' AND key = '{}' AND value = '{}' FORMAT JSONEachRow",
-            metric_name, tag_key, tag_value
-        );
-
-    let text = clickhouse_connection_info
-        .run_query_synchronous(query, None)
-        .await
-        .unwrap();
+    let text = Query::new(
+        "SELECT * FROM FeedbackTag WHERE metric_name = {metric_name:String} AND key = {tag_key:String} \
+         AND value = {tag_value:String} FORMAT JSONEachRow",
+    )
+    .bind("metric_name", metric_name)
+    .bind("tag_key", tag_key)
+    .bind("tag_value", tag_value)
+    .run(clickhouse_connection_info)
+    .await
+    .unwrap();
     let json: Value = serde_json::from_str(&text).ok()?;
     Some(json)
 }
@@ -428,31 +405,22 @@ pub async fn select_human_static_evaluation_feedback_clickhouse(
     datapoint_id: Uuid,
     output: &str,
 ) -> Option<StaticEvaluationHumanFeedback> {
-    let datapoint_id_str = datapoint_id.to_string();
     let escaped_output = escape_string_for_clickhouse_comparison(output);
-    let params = HashMap::from([
-        ("metric_name", metric_name),
-        ("datapoint_id", &datapoint_id_str),
-        ("output", &escaped_output),
-    ]);
-    let query = r#"
-        SELECT * FROM StaticEvaluationHumanFeedback
+    let query = Query::new(
+        "SELECT * FROM StaticEvaluationHumanFeedback
         WHERE
             metric_name = {metric_name:String}
             AND datapoint_id = {datapoint_id:UUID}
-            AND output = {output:String}
-        FORMAT JSONEachRow"#
-        .to_string();
-    let text = clickhouse_connection_info
-        .run_query_synchronous(query, Some(&params))
-        .await
-        .unwrap();
-    if text.is_empty() {
-        // Return None if the query returns no rows
-        None
-    } else {
-        // Panic if the query fails to parse or multiple rows are returned
-        let json: StaticEvaluationHumanFeedback = serde_json::from_str(&text).unwrap();
-        Some(json)
-    }
+            AND output = {output:String}",
+    )
+    .bind("metric_name", metric_name)
+    .bind("datapoint_id", datapoint_id)
+    .bind("output", escaped_output);
+    // Typed RowBinary decoding, rather than `FORMAT JSONEachRow` + `serde_json::from_str`,
+    // so a malformed row surfaces as an error instead of a silently dropped `None`.
+    let mut rows: Vec<StaticEvaluationHumanFeedback> =
+        query.run_typed(clickhouse_connection_info).await.unwrap();
+    // Panic if multiple rows are returned
+    assert!(rows.len() <= 1, "expected at most one row of human feedback");
+    rows.pop()
 }